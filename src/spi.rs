@@ -1,161 +1,511 @@
 //! BME280 driver for sensors attached via SPI.
 
-use embedded_hal::blocking::delay::DelayMs;
-use embedded_hal::blocking::spi::Transfer;
-use embedded_hal::digital::v2::OutputPin;
-
 use super::{
-    BME280Common, Error, Interface, Measurements, BME280_H_CALIB_DATA_LEN,
+    BME280Common, Configuration, Error, Interface, Measurements, Status, BME280_H_CALIB_DATA_LEN,
     BME280_P_T_CALIB_DATA_LEN, BME280_P_T_H_DATA_LEN,
 };
 
+/// Raw register access for a BME280 transport.
+///
+/// Implementors only speak registers; the measurement and calibration
+/// pipeline is supplied once by the blanket [`Interface`] impl below.
+/// [`SPIInterface`] applies the SPI `reg & 0x7f` write / `reg | 0x80` read
+/// address conventions, while the I2C side supplies its own addressing.
+pub trait Bus {
+    /// Error type of the underlying transport.
+    type Error;
+
+    /// Writes `value` to `register`.
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Error<Self::Error>>;
+
+    /// Reads a single byte from `register`.
+    fn read(&mut self, register: u8) -> Result<u8, Error<Self::Error>>;
+
+    /// Reads `data.len()` bytes in a single burst starting at `register`.
+    fn reads(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<Self::Error>>;
+}
+
+impl<B: Bus> Interface for B {
+    type Error = B::Error;
+
+    fn read_register(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
+        self.read(register)
+    }
+
+    fn read_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_P_T_H_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_P_T_H_DATA_LEN];
+        self.reads(register, &mut data)?;
+        Ok(data)
+    }
+
+    fn read_pt_calib_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_P_T_CALIB_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_P_T_CALIB_DATA_LEN];
+        self.reads(register, &mut data)?;
+        Ok(data)
+    }
+
+    fn read_h_calib_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_H_CALIB_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_H_CALIB_DATA_LEN];
+        self.reads(register, &mut data)?;
+        Ok(data)
+    }
+
+    fn write_register(&mut self, register: u8, payload: u8) -> Result<(), Error<Self::Error>> {
+        self.write(register, payload)
+    }
+}
+
+/// Async counterpart of [`Bus`]: raw register access for an async transport.
+///
+/// As with [`Bus`], implementors only speak registers; the measurement and
+/// calibration pipeline is supplied once by the blanket [`AsyncInterface`]
+/// impl below.
+#[cfg(feature = "async")]
+pub trait AsyncBus {
+    /// Error type of the underlying transport.
+    type Error;
+
+    /// Writes `value` to `register`.
+    async fn write(&mut self, register: u8, value: u8) -> Result<(), Error<Self::Error>>;
+
+    /// Reads a single byte from `register`.
+    async fn read(&mut self, register: u8) -> Result<u8, Error<Self::Error>>;
+
+    /// Reads `data.len()` bytes in a single burst starting at `register`.
+    async fn reads(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<Self::Error>>;
+}
+
+#[cfg(feature = "async")]
+impl<B: AsyncBus> super::AsyncInterface for B {
+    type Error = B::Error;
+
+    async fn read_register(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
+        self.read(register).await
+    }
+
+    async fn read_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_P_T_H_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_P_T_H_DATA_LEN];
+        self.reads(register, &mut data).await?;
+        Ok(data)
+    }
+
+    async fn read_pt_calib_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_P_T_CALIB_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_P_T_CALIB_DATA_LEN];
+        self.reads(register, &mut data).await?;
+        Ok(data)
+    }
+
+    async fn read_h_calib_data(
+        &mut self,
+        register: u8,
+    ) -> Result<[u8; BME280_H_CALIB_DATA_LEN], Error<Self::Error>> {
+        let mut data = [0; BME280_H_CALIB_DATA_LEN];
+        self.reads(register, &mut data).await?;
+        Ok(data)
+    }
+
+    async fn write_register(&mut self, register: u8, payload: u8) -> Result<(), Error<Self::Error>> {
+        self.write(register, payload).await
+    }
+}
+
+#[cfg(feature = "eh-0-2")]
+pub use self::eh_0_2::{DummyOutputPin, BME280 as LegacyBME280, SPIError};
+
+#[cfg(feature = "eh-0-2")]
+mod eh_0_2 {
+    use core::convert::Infallible;
+
+    use embedded_hal_0_2::blocking::delay::DelayMs;
+    use embedded_hal_0_2::blocking::spi::Transfer;
+    use embedded_hal_0_2::digital::v2::OutputPin;
+
+    use super::super::{BME280Common, Error, Measurements};
+    use super::Bus;
+
+    /// Representation of a BME280 driven over an `embedded-hal` 0.2 SPI bus.
+    #[derive(Debug, Default)]
+    pub struct BME280<SPI, CS, D> {
+        common: BME280Common<SPIInterface<SPI, CS>, D>,
+    }
+
+    impl<SPI, CS, D, SPIE, PinE> BME280<SPI, CS, D>
+    where
+        SPI: Transfer<u8, Error = SPIE>,
+        CS: OutputPin<Error = PinE>,
+        D: DelayMs<u8>,
+    {
+        /// Create a new BME280 struct
+        pub fn new(spi: SPI, mut cs: CS, delay: D) -> Result<Self, Error<SPIError<SPIE, PinE>>> {
+            // Deassert chip-select.
+            cs.set_high().map_err(|e| Error::Bus(SPIError::Pin(e)))?;
+
+            Ok(BME280 {
+                common: BME280Common {
+                    interface: SPIInterface { spi, cs },
+                    delay,
+                    calibration: None,
+                },
+            })
+        }
+
+        /// Initializes the BME280
+        pub fn init(&mut self) -> Result<(), Error<SPIError<SPIE, PinE>>> {
+            self.common.init()
+        }
+
+        /// Captures and processes sensor data for temperature, pressure, and humidity
+        pub fn measure(
+            &mut self,
+        ) -> Result<Measurements<SPIError<SPIE, PinE>>, Error<SPIError<SPIE, PinE>>> {
+            self.common.measure()
+        }
+
+        /// Destroys the object and returns the underlying interfaces.
+        ///
+        /// After this function has been called, the bus can be used for a different device.
+        pub fn destroy(self) -> (SPI, CS, D) {
+            (
+                self.common.interface.spi,
+                self.common.interface.cs,
+                self.common.delay,
+            )
+        }
+    }
+
+    /// Register access functions for SPI
+    #[derive(Debug, Default)]
+    struct SPIInterface<SPI, CS> {
+        /// concrete SPI device implementation
+        spi: SPI,
+        /// chip-select pin
+        cs: CS,
+    }
+
+    impl<SPI, CS> Bus for SPIInterface<SPI, CS>
+    where
+        SPI: Transfer<u8>,
+        CS: OutputPin,
+    {
+        type Error = SPIError<SPI::Error, CS::Error>;
+
+        fn write(&mut self, register: u8, value: u8) -> Result<(), Error<Self::Error>> {
+            self.cs
+                .set_low()
+                .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
+            // Clearing the top bit selects a write.
+            let mut transfer = [register & 0x7f, value];
+            self.spi
+                .transfer(&mut transfer)
+                .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
+            self.cs
+                .set_high()
+                .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
+            Ok(())
+        }
+
+        fn read(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
+            let mut data = [0u8];
+            self.reads(register, &mut data)?;
+            Ok(data[0])
+        }
+
+        fn reads(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<Self::Error>> {
+            self.cs
+                .set_low()
+                .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
+            // Setting the top bit selects a read.
+            let mut register = [register | 0x80];
+            self.spi
+                .transfer(&mut register)
+                .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
+            self.spi
+                .transfer(data)
+                .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
+            self.cs
+                .set_high()
+                .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
+            Ok(())
+        }
+    }
+
+    /// A chip-select pin that does nothing.
+    ///
+    /// Some MCUs drive the chip-select line in hardware as part of the SPI
+    /// peripheral. In that case there is no GPIO to hand to [`BME280::new`], so
+    /// pass a `DummyOutputPin` instead: its `set_high`/`set_low` are no-ops and
+    /// it never fails.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct DummyOutputPin;
+
+    impl OutputPin for DummyOutputPin {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Error which occurred during an SPI transaction
+    #[derive(Clone, Copy, Debug)]
+    pub enum SPIError<SPIE, PinE> {
+        /// The SPI implementation returned an error
+        SPI(SPIE),
+        /// The GPIO implementation returned an error which changing the chip-select pin state
+        Pin(PinE),
+    }
+}
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::spi::{Operation, SpiDevice};
+
 /// Representation of a BME280
+///
+/// Chip-select is managed by the [`SpiDevice`] implementation rather than a
+/// separate GPIO, so the same bus can be shared with other devices: the HAL
+/// serializes access and keeps CS asserted for the duration of each
+/// transaction.
 #[derive(Debug, Default)]
-pub struct BME280<SPI, CS, D> {
-    common: BME280Common<SPIInterface<SPI, CS>, D>,
+pub struct BME280<DEV, D> {
+    common: BME280Common<SPIInterface<DEV>, D>,
 }
 
-impl<SPI, CS, D, SPIE, PinE> BME280<SPI, CS, D>
+impl<DEV, D, E> BME280<DEV, D>
 where
-    SPI: Transfer<u8, Error = SPIE>,
-    CS: OutputPin<Error = PinE>,
-    D: DelayMs<u8>,
+    DEV: SpiDevice<u8, Error = E>,
+    D: DelayNs,
 {
     /// Create a new BME280 struct
-    pub fn new(spi: SPI, mut cs: CS, delay: D) -> Result<Self, Error<SPIError<SPIE, PinE>>> {
-        // Deassert chip-select.
-        cs.set_high().map_err(|e| Error::Bus(SPIError::Pin(e)))?;
-
+    pub fn new(dev: DEV, delay: D) -> Result<Self, Error<E>> {
         Ok(BME280 {
             common: BME280Common {
-                interface: SPIInterface { spi, cs },
+                interface: SPIInterface { dev },
                 delay,
                 calibration: None,
             },
         })
     }
 
-    /// Initializes the BME280
-    pub fn init(&mut self) -> Result<(), Error<SPIError<SPIE, PinE>>> {
+    /// Initializes the BME280 with the default [`Configuration`]
+    pub fn init(&mut self) -> Result<(), Error<E>> {
         self.common.init()
     }
 
+    /// Initializes the BME280, applying the given [`Configuration`]
+    pub fn init_with_config(&mut self, config: Configuration) -> Result<(), Error<E>> {
+        self.common.init_with_config(config)
+    }
+
+    /// Applies a new [`Configuration`] (oversampling, IIR filter, standby time, mode)
+    pub fn set_config(&mut self, config: Configuration) -> Result<(), Error<E>> {
+        self.common.set_config(config)
+    }
+
     /// Captures and processes sensor data for temperature, pressure, and humidity
-    pub fn measure(
-        &mut self,
-    ) -> Result<Measurements<SPIError<SPIE, PinE>>, Error<SPIError<SPIE, PinE>>> {
+    pub fn measure(&mut self) -> Result<Measurements<E>, Error<E>> {
         self.common.measure()
     }
 
+    /// Reads the latest conversion without re-triggering a measurement.
+    ///
+    /// Intended for normal (free-running) mode, where the sensor measures
+    /// continuously: this skips the forced-mode trigger and the fixed
+    /// post-trigger delay of [`measure`](Self::measure).
+    pub fn get_measurement(&mut self) -> Result<Measurements<E>, Error<E>> {
+        self.common.get_measurement()
+    }
+
+    /// Reads the `measuring` and `im_update` bits of the status register (0xF3).
+    ///
+    /// Lets callers poll for data-ready instead of always delaying.
+    pub fn status(&mut self) -> Result<Status, Error<E>> {
+        self.common.status()
+    }
+
     /// Destroys the object and returns the underlying interfaces.
     ///
     /// After this function has been called, the bus can be used for a different device.
-    pub fn destroy(self) -> (SPI, CS, D) {
-        (
-            self.common.interface.spi,
-            self.common.interface.cs,
-            self.common.delay,
-        )
+    pub fn destroy(self) -> (DEV, D) {
+        (self.common.interface.dev, self.common.delay)
     }
 }
 
 /// Register access functions for SPI
 #[derive(Debug, Default)]
-struct SPIInterface<SPI, CS> {
+struct SPIInterface<DEV> {
     /// concrete SPI device implementation
-    spi: SPI,
-    /// chip-select pin
-    cs: CS,
+    dev: DEV,
 }
 
-impl<SPI, CS> Interface for SPIInterface<SPI, CS>
+impl<DEV, E> Bus for SPIInterface<DEV>
 where
-    SPI: Transfer<u8>,
-    CS: OutputPin,
+    DEV: SpiDevice<u8, Error = E>,
 {
-    type Error = SPIError<SPI::Error, CS::Error>;
+    type Error = E;
 
-    fn read_register(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
-        let mut result = [0u8];
-        self.read_any_register(register, &mut result)?;
-        Ok(result[0])
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Error<Self::Error>> {
+        // Clearing the top bit selects a write. Address and payload are
+        // submitted as a single transaction so chip-select stays asserted for
+        // the whole exchange.
+        self.dev
+            .transaction(&mut [Operation::Write(&[register & 0x7f, value])])
+            .map_err(Error::Bus)
     }
 
-    fn read_data(
-        &mut self,
-        register: u8,
-    ) -> Result<[u8; BME280_P_T_H_DATA_LEN], Error<Self::Error>> {
-        let mut data: [u8; BME280_P_T_H_DATA_LEN] = [0; BME280_P_T_H_DATA_LEN];
-        self.read_any_register(register, &mut data)?;
-        Ok(data)
+    fn read(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
+        let mut data = [0u8];
+        self.reads(register, &mut data)?;
+        Ok(data[0])
     }
 
-    fn read_pt_calib_data(
-        &mut self,
-        register: u8,
-    ) -> Result<[u8; BME280_P_T_CALIB_DATA_LEN], Error<Self::Error>> {
-        let mut data: [u8; BME280_P_T_CALIB_DATA_LEN] = [0; BME280_P_T_CALIB_DATA_LEN];
-        self.read_any_register(register, &mut data)?;
-        Ok(data)
+    fn reads(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<Self::Error>> {
+        // Setting the top bit selects a read. Address and body go in one
+        // transaction so the HAL holds chip-select low across both operations,
+        // keeping the contiguous 0xF7 burst read intact on a shared bus.
+        self.dev
+            .transaction(&mut [Operation::Write(&[register | 0x80]), Operation::Read(data)])
+            .map_err(Error::Bus)
     }
+}
 
-    fn read_h_calib_data(
-        &mut self,
-        register: u8,
-    ) -> Result<[u8; BME280_H_CALIB_DATA_LEN], Error<Self::Error>> {
-        let mut data: [u8; BME280_H_CALIB_DATA_LEN] = [0; BME280_H_CALIB_DATA_LEN];
-        self.read_any_register(register, &mut data)?;
-        Ok(data)
+#[cfg(feature = "async")]
+pub use self::asynch::BME280 as AsyncBME280;
+
+/// Asynchronous BME280 driver built on `embedded-hal-async`.
+///
+/// The forced-mode flow has to wait for a conversion to finish after it is
+/// triggered. The blocking drivers burn that time in a blocking delay, stalling
+/// the whole task; here the delay is `.await`-ed instead, so a BME280 can share an
+/// executor with other concurrent I/O.
+#[cfg(feature = "async")]
+mod asynch {
+    use embedded_hal_async::delay::DelayNs;
+    use embedded_hal_async::spi::{Operation, SpiDevice};
+
+    use super::super::{BME280Common, Configuration, Error, Measurements, Status};
+    use super::AsyncBus;
+
+    /// Representation of a BME280
+    #[derive(Debug, Default)]
+    pub struct BME280<DEV, D> {
+        common: BME280Common<SPIInterface<DEV>, D>,
     }
 
-    fn write_register(&mut self, register: u8, payload: u8) -> Result<(), Error<Self::Error>> {
-        self.cs
-            .set_low()
-            .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
-        // If the first bit is 0, the register is written.
-        let mut transfer = [register & 0x7f, payload];
-        self.spi
-            .transfer(&mut transfer)
-            .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
-        self.cs
-            .set_high()
-            .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
-        Ok(())
+    impl<DEV, D, E> BME280<DEV, D>
+    where
+        DEV: SpiDevice<u8, Error = E>,
+        D: DelayNs,
+    {
+        /// Create a new BME280 struct
+        pub fn new(dev: DEV, delay: D) -> Result<Self, Error<E>> {
+            Ok(BME280 {
+                common: BME280Common {
+                    interface: SPIInterface { dev },
+                    delay,
+                    calibration: None,
+                },
+            })
+        }
+
+        /// Initializes the BME280 with the default [`Configuration`]
+        pub async fn init(&mut self) -> Result<(), Error<E>> {
+            self.common.init().await
+        }
+
+        /// Initializes the BME280, applying the given [`Configuration`]
+        pub async fn init_with_config(&mut self, config: Configuration) -> Result<(), Error<E>> {
+            self.common.init_with_config(config).await
+        }
+
+        /// Applies a new [`Configuration`] (oversampling, IIR filter, standby time, mode)
+        pub async fn set_config(&mut self, config: Configuration) -> Result<(), Error<E>> {
+            self.common.set_config(config).await
+        }
+
+        /// Captures and processes sensor data for temperature, pressure, and humidity
+        pub async fn measure(&mut self) -> Result<Measurements<E>, Error<E>> {
+            self.common.measure().await
+        }
+
+        /// Reads the latest conversion without re-triggering a measurement.
+        ///
+        /// Intended for normal (free-running) mode: skips the forced-mode
+        /// trigger and the fixed post-trigger delay of
+        /// [`measure`](Self::measure).
+        pub async fn get_measurement(&mut self) -> Result<Measurements<E>, Error<E>> {
+            self.common.get_measurement().await
+        }
+
+        /// Reads the `measuring` and `im_update` bits of the status register (0xF3).
+        pub async fn status(&mut self) -> Result<Status, Error<E>> {
+            self.common.status().await
+        }
+
+        /// Destroys the object and returns the underlying interfaces.
+        ///
+        /// After this function has been called, the bus can be used for a different device.
+        pub fn destroy(self) -> (DEV, D) {
+            (self.common.interface.dev, self.common.delay)
+        }
     }
-}
 
-impl<SPI, CS> SPIInterface<SPI, CS>
-where
-    SPI: Transfer<u8>,
-    CS: OutputPin,
-{
-    fn read_any_register(
-        &mut self,
-        register: u8,
-        data: &mut [u8],
-    ) -> Result<(), Error<SPIError<SPI::Error, CS::Error>>> {
-        self.cs
-            .set_low()
-            .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
-        let mut register = [register];
-        self.spi
-            .transfer(&mut register)
-            .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
-        self.spi
-            .transfer(data)
-            .map_err(|e| Error::Bus(SPIError::SPI(e)))?;
-        self.cs
-            .set_high()
-            .map_err(|e| Error::Bus(SPIError::Pin(e)))?;
-        Ok(())
+    /// Register access functions for SPI
+    #[derive(Debug, Default)]
+    struct SPIInterface<DEV> {
+        /// concrete SPI device implementation
+        dev: DEV,
     }
-}
 
-/// Error which occurred during an SPI transaction
-#[derive(Clone, Copy, Debug)]
-pub enum SPIError<SPIE, PinE> {
-    /// The SPI implementation returned an error
-    SPI(SPIE),
-    /// The GPIO implementation returned an error which changing the chip-select pin state
-    Pin(PinE),
+    impl<DEV, E> AsyncBus for SPIInterface<DEV>
+    where
+        DEV: SpiDevice<u8, Error = E>,
+    {
+        type Error = E;
+
+        async fn write(&mut self, register: u8, value: u8) -> Result<(), Error<Self::Error>> {
+            // Clearing the top bit selects a write. Address and payload are
+            // submitted as a single transaction so chip-select stays asserted
+            // for the whole exchange.
+            self.dev
+                .transaction(&mut [Operation::Write(&[register & 0x7f, value])])
+                .await
+                .map_err(Error::Bus)
+        }
+
+        async fn read(&mut self, register: u8) -> Result<u8, Error<Self::Error>> {
+            let mut data = [0u8];
+            self.reads(register, &mut data).await?;
+            Ok(data[0])
+        }
+
+        async fn reads(&mut self, register: u8, data: &mut [u8]) -> Result<(), Error<Self::Error>> {
+            // Setting the top bit selects a read. Address and body go in one
+            // transaction so the HAL holds chip-select low across both
+            // operations, keeping the contiguous 0xF7 burst read intact on a
+            // shared bus.
+            self.dev
+                .transaction(&mut [Operation::Write(&[register | 0x80]), Operation::Read(data)])
+                .await
+                .map_err(Error::Bus)
+        }
+    }
 }